@@ -2,11 +2,15 @@
 //!
 //! A combination of the various resource driver park handles.
 
-use crate::loom::sync::Arc;
-use crate::loom::sync::atomic::AtomicBool;
+use crate::loom::sync::atomic::AtomicUsize;
+use crate::loom::sync::{Arc, Condvar, Mutex};
+#[cfg(not(test))]
 use crate::runtime::io;
+#[cfg(test)]
+use self::tests::mock_io as io;
 
 use std::sync::atomic::Ordering::SeqCst;
+use std::time::Duration;
 
 pub(crate) struct Parker {
     inner: Arc<Inner>,
@@ -16,19 +20,26 @@ pub(crate) struct Unparker {
     inner: Arc<Inner>,
 }
 
+/// Error returned by `Parker::park`.
+///
+/// Currently, parking cannot actually fail, but the type is kept around so a
+/// future failure mode (e.g. a broken I/O driver) does not need to change
+/// the public signature.
+#[derive(Debug)]
+pub(crate) struct ParkError(());
+
 struct Inner {
     /// Avoids entering the park if possible
     state: AtomicUsize,
 
-    /// Used to coordinate access to the driver / condvar
-    ///
-    /// The state is `true` when the thread is parked in the driver.
-    mutex: Mutex<bool>,
+    /// Guards the condvar; only used along the `SLEEP_CONDVAR` path.
+    mutex: Mutex<()>,
 
     /// Condvar to block on if the driver is unavailable.
     condvar: Condvar,
 
-    /// Resource (I/O, time, ...) driver
+    /// Resource (I/O, time, ...) driver, shared by every `Parker` cloned
+    /// from the same `Inner::driver`.
     driver: Arc<Driver>,
 }
 
@@ -37,9 +48,15 @@ const NOTIFY: usize = 1;
 const SLEEP_CONDVAR: usize = 2;
 const SLEEP_DRIVER: usize = 3;
 
+const UNLOCKED: usize = 0;
+const LOCKED: usize = 1;
+
 /// Synchronizes access to the shared resource drivers.
 struct Driver {
-    /// Coordinates access to the driver
+    /// Coordinates access to the driver. Whichever parking thread manages to
+    /// flip this from `UNLOCKED` to `LOCKED` is the one that parks in
+    /// `driver` directly for that round; everyone else falls back to the
+    /// condvar.
     lock: AtomicUsize,
 
     /// Shared driver.
@@ -49,18 +66,81 @@ struct Driver {
     handle: io::Handle,
 }
 
+impl Parker {
+    /// Create a new `Parker` backed by `driver`, the first of what may be
+    /// several `Parker`s sharing the same underlying I/O driver.
+    pub(crate) fn new(driver: io::Driver) -> Parker {
+        let handle = driver.handle();
+
+        Parker {
+            inner: Arc::new(Inner {
+                state: AtomicUsize::new(IDLE),
+                mutex: Mutex::new(()),
+                condvar: Condvar::new(),
+                driver: Arc::new(Driver {
+                    lock: AtomicUsize::new(UNLOCKED),
+                    driver,
+                    handle,
+                }),
+            }),
+        }
+    }
+
+    /// Create another `Parker` that competes with this one (and any other
+    /// `Parker` cloned from it) for ownership of the shared I/O driver.
+    pub(crate) fn clone(&self) -> Parker {
+        Parker {
+            inner: Arc::new(Inner {
+                state: AtomicUsize::new(IDLE),
+                mutex: Mutex::new(()),
+                condvar: Condvar::new(),
+                driver: self.inner.driver.clone(),
+            }),
+        }
+    }
+
+    pub(crate) fn unpark(&self) -> Unparker {
+        Unparker {
+            inner: self.inner.clone(),
+        }
+    }
+
+    pub(crate) fn park(&mut self) {
+        self.inner.park(None).unwrap();
+    }
+
+    pub(crate) fn park_timeout(&mut self, duration: Duration) {
+        self.inner.park(Some(duration)).unwrap();
+    }
+}
+
+impl Unparker {
+    pub(crate) fn unpark(&self) {
+        self.inner.unpark();
+    }
+}
+
 impl Inner {
-    /// Park the current thread for at most `dur`.
+    /// Park the current thread for at most `timeout`.
     fn park(&self, timeout: Option<Duration>) -> Result<(), ParkError> {
-        // If currently notified, then we skip sleeping. This is checked outside
-        // of the lock to avoid acquiring a mutex if not necessary.
+        // If currently notified, then we skip sleeping. This is checked
+        // outside of the driver lock to avoid contending for it if not
+        // necessary.
         match self.state.compare_and_swap(NOTIFY, IDLE, SeqCst) {
             NOTIFY => return Ok(()),
             IDLE => {}
             _ => unreachable!(),
         }
 
-        self.park_condvar()
+        // Try to become the thread that owns the shared I/O driver for this
+        // round. Only the thread that wins the CAS may touch `self.driver.driver`.
+        if self.driver.lock.compare_and_swap(UNLOCKED, LOCKED, SeqCst) == UNLOCKED {
+            let res = self.park_driver(timeout);
+            self.driver.lock.store(UNLOCKED, SeqCst);
+            res
+        } else {
+            self.park_condvar(timeout)
+        }
     }
 
     fn park_condvar(&self, timeout: Option<Duration>) -> Result<(), ParkError> {
@@ -69,7 +149,7 @@ impl Inner {
         let mut m = self.mutex.lock().unwrap();
 
         // Transition to sleeping
-        match self.state.compare_and_swap(IDLE, SLEEP, SeqCst) {
+        match self.state.compare_and_swap(IDLE, SLEEP_CONDVAR, SeqCst) {
             NOTIFY => {
                 // Notified before we could sleep, consume the notification and
                 // exit
@@ -97,33 +177,213 @@ impl Inner {
         Ok(())
     }
 
+    /// Park in the shared I/O driver. Only called by the thread that holds
+    /// `self.driver.lock`.
+    fn park_driver(&self, timeout: Option<Duration>) -> Result<(), ParkError> {
+        // Transition to sleeping
+        match self.state.compare_and_swap(IDLE, SLEEP_DRIVER, SeqCst) {
+            NOTIFY => {
+                // Notified before we could sleep, consume the notification and
+                // exit
+                self.state.store(IDLE, SeqCst);
+                return Ok(());
+            }
+            IDLE => {}
+            _ => unreachable!(),
+        }
+
+        self.driver
+            .driver
+            .park(timeout)
+            .expect("I/O driver failed to park");
+
+        // Transition back to idle. If the state has transitioned to `NOTIFY`,
+        // this will consume that notification.
+        self.state.store(IDLE, SeqCst);
+
+        Ok(())
+    }
+
     fn unpark(&self) {
         // First, try transitioning from IDLE -> NOTIFY, this does not require a
         // lock.
-        match self.state.compare_and_swap(IDLE, NOTIFY, SeqCst) {
-            IDLE | NOTIFY => return,
-            SLEEP => {}
+        match self.state.swap(NOTIFY, SeqCst) {
+            IDLE | NOTIFY => {}
+            SLEEP_CONDVAR => self.unpark_condvar(),
+            SLEEP_DRIVER => self.unpark_driver(),
             _ => unreachable!(),
         }
+    }
 
-        // The other half is sleeping, this requires a lock
+    fn unpark_condvar(&self) {
+        // The sleeper is blocked on the condvar; acquire the same mutex
+        // before notifying so the wakeup is never missed.
         let _m = self.mutex.lock().unwrap();
 
-        // Transition to NOTIFY
-        match self.state.swap(NOTIFY, SeqCst) {
-            SLEEP => {}
-            NOTIFY => return,
-            IDLE => return,
-            _ => unreachable!(),
+        self.condvar.notify_one();
+    }
+
+    fn unpark_driver(&self) {
+        self.driver.handle.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    /// A stand-in for `crate::runtime::io`, real enough to block and wake a
+    /// thread through `Inner::park_driver`/`unpark_driver` without pulling in
+    /// an actual I/O driver. Only used by this module's tests; production
+    /// code always goes through the real `crate::runtime::io`.
+    pub(super) mod mock_io {
+        use crate::loom::sync::{Arc, Condvar, Mutex};
+        use std::time::Duration;
+
+        #[derive(Default)]
+        struct State {
+            woken: bool,
         }
 
-        // Wakeup the sleeper
-        self.condvar.notify_one();
+        pub(crate) struct Driver {
+            inner: Arc<(Mutex<State>, Condvar)>,
+        }
+
+        impl Driver {
+            pub(crate) fn new() -> Driver {
+                Driver {
+                    inner: Arc::new((Mutex::new(State::default()), Condvar::new())),
+                }
+            }
+
+            pub(crate) fn handle(&self) -> Handle {
+                Handle {
+                    inner: self.inner.clone(),
+                }
+            }
+
+            pub(crate) fn park(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+                let (mutex, condvar) = &*self.inner;
+                let mut state = mutex.lock().unwrap();
+
+                while !state.woken {
+                    match timeout {
+                        Some(timeout) => {
+                            let (guard, result) =
+                                condvar.wait_timeout(state, timeout).unwrap();
+                            state = guard;
+                            if result.timed_out() {
+                                break;
+                            }
+                        }
+                        None => state = condvar.wait(state).unwrap(),
+                    }
+                }
+
+                state.woken = false;
+                Ok(())
+            }
+        }
+
+        #[derive(Clone)]
+        pub(crate) struct Handle {
+            inner: Arc<(Mutex<State>, Condvar)>,
+        }
+
+        impl Handle {
+            pub(crate) fn unpark(&self) {
+                let (mutex, condvar) = &*self.inner;
+                mutex.lock().unwrap().woken = true;
+                condvar.notify_one();
+            }
+        }
     }
 
-    fn unpark_condvar(&self) {
+    fn new_parker() -> Parker {
+        Parker::new(mock_io::Driver::new())
     }
 
-    fn unpark_driver(&self) {
+    /// Spawn a thread that parks `parker`, signalling over the returned
+    /// channel once `park` returns. Used to assert a parked thread woke up
+    /// rather than hanging forever, without the test itself blocking
+    /// indefinitely if it didn't.
+    fn park_in_background(mut parker: Parker) -> mpsc::Receiver<()> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            parker.park();
+            let _ = tx.send(());
+        });
+        rx
+    }
+
+    /// Spins until `unparker`'s shared `state` reports `expected`, i.e. the
+    /// thread it belongs to has actually entered `park_driver`/
+    /// `park_condvar` rather than just having been spawned. Panics rather
+    /// than looping forever if that never happens, so a regression shows up
+    /// as a test failure instead of a hang.
+    fn wait_until_parked(unparker: &Unparker, expected: usize) {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while unparker.inner.state.load(SeqCst) != expected {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "thread never reached the expected park state"
+            );
+            std::thread::yield_now();
+        }
+    }
+
+    /// Exercises both paths `park` can take: the first `Parker` to call
+    /// `park` wins the race for the shared driver and blocks in
+    /// `park_driver`, while a second `Parker` cloned from it (and so
+    /// contending for the same driver) falls back to `park_condvar`.
+    /// `unpark` on each must wake its respective thread.
+    #[test]
+    fn concurrent_park_and_unpark_wake_both_driver_and_condvar_paths() {
+        let driver_parker = new_parker();
+        let condvar_parker = driver_parker.clone();
+
+        let driver_unparker = driver_parker.unpark();
+        let condvar_unparker = condvar_parker.unpark();
+
+        // Wait for each background thread to actually be parked in the
+        // path under test, rather than guessing with a fixed sleep;
+        // otherwise `unpark` below could race ahead of the `park` it's
+        // meant to wake (which `park`'s own IDLE/NOTIFY handling, not what's
+        // under test here, already covers) and the test would pass without
+        // ever exercising `park_driver`/`park_condvar` at all. The condvar
+        // parker is only started once the driver parker holds the driver,
+        // so `driver_parker` is the one that parks first and wins it.
+        let driver_done = park_in_background(driver_parker);
+        wait_until_parked(&driver_unparker, SLEEP_DRIVER);
+
+        let condvar_done = park_in_background(condvar_parker);
+        wait_until_parked(&condvar_unparker, SLEEP_CONDVAR);
+
+        driver_unparker.unpark();
+        condvar_unparker.unpark();
+
+        driver_done
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the thread parked in the shared driver never woke up");
+        condvar_done
+            .recv_timeout(Duration::from_secs(5))
+            .expect("the thread parked on the condvar never woke up");
+    }
+
+    /// `unpark` called before the matching `park` must still be observed:
+    /// the NOTIFY state it leaves behind is consumed by the next `park`
+    /// instead of blocking.
+    #[test]
+    fn unpark_before_park_is_not_lost() {
+        let mut parker = new_parker();
+        let unparker = parker.unpark();
+
+        unparker.unpark();
+
+        // Must return immediately; if `unpark` were lost, this would hang.
+        parker.park();
     }
 }
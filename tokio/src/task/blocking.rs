@@ -0,0 +1,61 @@
+use crate::runtime::context;
+use crate::task::JoinHandle;
+
+/// Runs the provided closure on a thread where blocking is acceptable.
+///
+/// In general, issuing a blocking call or performing a lot of compute in a
+/// future without yielding is problematic, as it may prevent the executor
+/// from driving other futures forward. This function runs the provided
+/// closure on a thread dedicated to blocking operations, which does not
+/// contribute to starving the runtime's other tasks.
+///
+/// # Examples
+///
+/// ```
+/// use tokio::task;
+///
+/// # async fn docs() {
+/// let join = task::spawn_blocking(move || {
+///     // do some compute-heavy work or call synchronous code
+///     "blocking completed"
+/// });
+///
+/// let result = join.await.unwrap();
+/// assert_eq!(result, "blocking completed");
+/// # }
+/// ```
+///
+/// # Panics
+///
+/// Panics if called from **outside** of a Tokio runtime.
+pub fn spawn_blocking<F, R>(func: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let blocking_handle = context::blocking_handle()
+        .expect("must be called from the context of a Tokio 1.x runtime");
+    blocking_handle.spawn_blocking(func)
+}
+
+cfg_rt_threaded! {
+    /// Run the provided blocking function without blocking the executor.
+    ///
+    /// In general, issuing a blocking call or performing a lot of compute in a
+    /// future without yielding is problematic, as it may prevent the executor
+    /// from driving other futures forward. Calling this function informs the
+    /// executor that the currently executing task is about to block the
+    /// thread, so it can hand off its other pending tasks to another worker
+    /// thread before `f` runs.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if called from a runtime that is not a
+    /// multi-threaded runtime.
+    pub fn block_in_place<F, R>(f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        context::block_in_place(f)
+    }
+}
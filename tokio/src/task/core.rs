@@ -0,0 +1,105 @@
+//! The layout of a task's heap allocation.
+//!
+//! A single allocation (a `Box<Cell<T, S>>`) backs both the `Task` handle and
+//! the `JoinHandle`. `Header` is the `#[repr(C)]`-stable prefix of that
+//! allocation; it is the only part of the layout that `RawTask` is allowed
+//! to touch without knowing `T` or `S`, which is what lets a `Task<S>` be
+//! represented as a type-erased `NonNull<Header>`.
+
+use crate::task::raw::Vtable;
+use crate::task::state::State;
+
+use std::cell::UnsafeCell;
+use std::future::Future;
+use std::ptr::NonNull;
+use std::task::Waker;
+
+/// The task cell. This is the actual heap allocation backing a task.
+#[repr(C)]
+pub(super) struct Cell<T: Future, S> {
+    /// Hot path of polling the task.
+    pub(super) header: Header,
+
+    /// Either the future or the output, plus scheduler-private state.
+    pub(super) core: Core<T, S>,
+}
+
+/// The core of the task.
+///
+/// Holds the future or finished output, and the `Schedule` implementation
+/// the task was spawned onto.
+pub(super) struct Core<T: Future, S> {
+    /// Pointer to the scheduler the task is bound to, set on first poll via
+    /// `Schedule::bind`. Not owned: the scheduler itself (typically an
+    /// `Arc`-like handle held by the runtime) is guaranteed to outlive every
+    /// task spawned onto it.
+    pub(super) scheduler: UnsafeCell<Option<NonNull<S>>>,
+
+    /// The future, or its output once it has completed.
+    pub(super) stage: UnsafeCell<Stage<T>>,
+}
+
+/// Either the future that the task is running, or the output produced once
+/// it has finished (successfully, via panic, or via cancellation).
+pub(super) enum Stage<T: Future> {
+    Running(T),
+    Finished(super::Result<T::Output>),
+    Consumed,
+}
+
+/// The type-erased, `repr(C)` prefix of every task allocation.
+///
+/// A pointer to a `Header` is how tasks are referred to once their concrete
+/// `T`/`S` types have been erased (e.g. by `Task::into_raw`).
+pub(crate) struct Header {
+    /// Task state, tracked via an atomic state machine.
+    pub(crate) state: State,
+
+    /// Table of type-erased functions used to drive the task.
+    pub(crate) vtable: &'static Vtable,
+
+    /// Intrusive next-pointer used by `TransferStack` when this task sits in
+    /// a scheduler's run queue.
+    pub(crate) queue_next: UnsafeCell<Option<NonNull<Header>>>,
+
+    /// Intrusive links used by `OwnedList` to track every task owned by a
+    /// scheduler, independent of whether it is currently queued to run.
+    pub(crate) owned_next: UnsafeCell<Option<NonNull<Header>>>,
+    pub(crate) owned_prev: UnsafeCell<Option<NonNull<Header>>>,
+
+    /// Waker notified when the task completes and a `JoinHandle` is parked
+    /// on it.
+    pub(crate) join_waker: UnsafeCell<Option<Waker>>,
+
+    /// Span this task is polled under. Only present when built with the
+    /// `tracing` feature; see `task::trace`.
+    #[cfg(feature = "tracing")]
+    pub(crate) span: tracing::Span,
+}
+
+// `Header` is read and written from whichever thread currently owns the
+// task; all cross-thread access goes through `State`'s atomics to establish
+// the needed happens-before edges, so it is safe to share by reference.
+unsafe impl Sync for Header {}
+
+impl<T: Future, S> Cell<T, S> {
+    /// Allocate a new task cell for `future`, with the given initial state.
+    pub(super) fn new(future: T, state: State, vtable: &'static Vtable) -> Box<Cell<T, S>> {
+        Box::new(Cell {
+            header: Header {
+                state,
+                vtable,
+                queue_next: UnsafeCell::new(None),
+                owned_next: UnsafeCell::new(None),
+                owned_prev: UnsafeCell::new(None),
+                join_waker: UnsafeCell::new(None),
+                #[cfg(feature = "tracing")]
+                span: crate::task::trace::new_span(),
+            },
+            core: Core {
+                scheduler: UnsafeCell::new(None),
+                stage: UnsafeCell::new(Stage::Running(future)),
+            },
+        })
+    }
+}
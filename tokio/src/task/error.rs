@@ -0,0 +1,114 @@
+use std::any::Any;
+use std::fmt;
+
+/// Task failed to execute to completion.
+pub struct JoinError {
+    repr: Repr,
+}
+
+enum Repr {
+    Cancelled,
+    Panic(Box<dyn Any + Send + 'static>),
+}
+
+impl JoinError {
+    pub(crate) fn cancelled() -> JoinError {
+        JoinError {
+            repr: Repr::Cancelled,
+        }
+    }
+
+    pub(crate) fn panic(payload: Box<dyn Any + Send + 'static>) -> JoinError {
+        JoinError {
+            repr: Repr::Panic(payload),
+        }
+    }
+
+    /// Returns `true` if the error was caused by the task being cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.repr, Repr::Cancelled)
+    }
+
+    /// Returns `true` if the error was caused by the task panicking.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio::task;
+    ///
+    /// # async fn dox() {
+    /// let join_handle = task::spawn(async {
+    ///     panic!("boom");
+    /// });
+    ///
+    /// let err = join_handle.await.unwrap_err();
+    /// assert!(err.is_panic());
+    /// # }
+    /// ```
+    pub fn is_panic(&self) -> bool {
+        matches!(self.repr, Repr::Panic(_))
+    }
+
+    /// Consumes the `JoinError`, returning the object with which the task
+    /// panicked.
+    ///
+    /// # Panics
+    ///
+    /// `into_panic()` panics if the `Error` does not represent the underlying
+    /// task terminating with a panic. Use `is_panic` to check the error
+    /// reason or `try_into_panic` for a variant that does not panic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tokio::task;
+    ///
+    /// # async fn dox() {
+    /// let join_handle = task::spawn(async {
+    ///     panic!("boom");
+    /// });
+    ///
+    /// let err = join_handle.await.unwrap_err();
+    /// if err.is_panic() {
+    ///     let panic_obj = err.into_panic();
+    ///     if let Some(reason) = panic_obj.downcast_ref::<&str>() {
+    ///         println!("Task panicked with reason: {}", reason);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        self.try_into_panic()
+            .unwrap_or_else(|_| panic!("`JoinError` reason is not a panic"))
+    }
+
+    /// Attempts to turn the `JoinError` into the object with which the task
+    /// panicked, if the task did in fact panic, returning the original
+    /// `JoinError` if the task was cancelled instead.
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send + 'static>, JoinError> {
+        match self.repr {
+            Repr::Panic(payload) => Ok(payload),
+            repr => Err(JoinError { repr }),
+        }
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => write!(fmt, "task was cancelled"),
+            Repr::Panic(_) => write!(fmt, "task panicked"),
+        }
+    }
+}
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.repr {
+            Repr::Cancelled => fmt.debug_tuple("JoinError::Cancelled").finish(),
+            Repr::Panic(_) => fmt.debug_tuple("JoinError::Panic").field(&"...").finish(),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
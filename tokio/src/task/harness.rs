@@ -0,0 +1,271 @@
+//! Glues a `Cell<T, S>` allocation to the type-erased operations `RawTask`
+//! needs to perform on it. `Harness` is never stored anywhere; it is
+//! reconstructed from a `NonNull<Header>` for the duration of a single
+//! operation (poll, cancel, drop, ...).
+
+use crate::task::core::{Cell, Core, Header, Stage};
+use crate::task::error::JoinError;
+use crate::task::waker::waker_ref;
+use crate::task::{Schedule, Task};
+
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::task::{Context, Poll, Waker};
+
+pub(super) struct Harness<T: Future + 'static, S: 'static> {
+    cell: NonNull<Cell<T, S>>,
+}
+
+impl<T, S> Harness<T, S>
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    pub(super) unsafe fn from_raw(ptr: NonNull<Header>) -> Harness<T, S> {
+        Harness { cell: ptr.cast() }
+    }
+
+    fn header(&self) -> &Header {
+        unsafe { &self.cell.as_ref().header }
+    }
+
+    fn core(&self) -> &Core<T, S> {
+        unsafe { &self.cell.as_ref().core }
+    }
+
+    /// Poll the task's future.
+    ///
+    /// Returns `true` if the task needs to be immediately rescheduled
+    /// (i.e. it was woken while it was already polling).
+    pub(super) unsafe fn poll(self, executor: &mut dyn FnMut() -> Option<NonNull<()>>) -> bool {
+        // A task can be cancelled before it is ever polled, or while it sits
+        // idle between polls. Check first, before touching the future at
+        // all, so a cancelled task that never yields still unblocks its
+        // `JoinHandle`.
+        if self.header().state.load().is_cancelled() {
+            self.complete(Err(JoinError::cancelled()));
+            return false;
+        }
+
+        self.bind_scheduler(executor);
+
+        let res = {
+            let waker = waker_ref::<T, S>(self.header());
+            let mut cx = Context::from_waker(&waker);
+            let stage = &mut *self.core().stage.get();
+
+            match stage {
+                Stage::Running(future) => {
+                    let future = Pin::new_unchecked(future);
+                    panic::catch_unwind(AssertUnwindSafe(|| future.poll(&mut cx)))
+                }
+                _ => unreachable!("poll called on a task that has already completed"),
+            }
+        };
+
+        match res {
+            Ok(Poll::Ready(output)) => {
+                self.complete(Ok(output));
+                false
+            }
+            Ok(Poll::Pending) => {
+                // The task may have been cancelled while it was polling;
+                // catch that here too rather than waiting for a wakeup that
+                // may never come if the future doesn't poll any more
+                // wakers.
+                if self.header().state.load().is_cancelled() {
+                    self.complete(Err(JoinError::cancelled()));
+                    return false;
+                }
+
+                self.header().state.transition_to_idle().is_notified()
+            }
+            Err(panic) => {
+                self.complete(Err(JoinError::panic(panic)));
+                false
+            }
+        }
+    }
+
+    /// Cancel the task, as requested by `JoinHandle::abort`/`AbortHandle::abort`.
+    pub(super) unsafe fn cancel(self) {
+        let snapshot = self.header().state.transition_to_cancelled();
+
+        if snapshot.is_complete() {
+            // Already finished (successfully, via panic, or via a previous
+            // cancellation); nothing to do.
+            return;
+        }
+
+        if !snapshot.is_running() {
+            // Nobody is currently polling (or about to poll) this task, so
+            // it will not notice `CANCELLED` on its own. Finish it now.
+            //
+            // `transition_to_cancelled` claims completion atomically: for an
+            // idle task, it sets `COMPLETE` together with `CANCELLED` in the
+            // same compare-and-swap, and only the one caller whose CAS
+            // performs that not-complete -> complete transition gets back a
+            // `snapshot` with neither bit set (checked above). Any other
+            // caller racing to cancel the same idle task instead observes
+            // `COMPLETE` already set in its own snapshot and returns before
+            // reaching here, so this call is guaranteed to be the only one
+            // that calls `complete` for this task.
+            //
+            // Unlike `poll`/`shutdown`, this path is reached through `&self`
+            // (from `JoinHandle::abort`/`AbortHandle::abort`), not through an
+            // owned `Task<S>` that a caller forgets into `complete`'s
+            // unconditional `ref_dec`. Acquire a reference of our own first
+            // so that `ref_dec` releases it rather than one that is still
+            // live elsewhere (the `JoinHandle` itself, or another
+            // `AbortHandle`).
+            self.header().state.ref_inc();
+            self.complete(Err(JoinError::cancelled()));
+        }
+
+        // If the task is running, the in-flight (or about-to-start) poll
+        // will see `CANCELLED` and finish on its own.
+    }
+
+    /// Force the task to finish as cancelled right away.
+    ///
+    /// # Safety
+    ///
+    /// The caller must know nothing else can be concurrently polling this
+    /// task (e.g. it was just removed from a run queue that is being
+    /// drained, rather than actively driven).
+    pub(super) unsafe fn shutdown(self) {
+        if self.header().state.load().is_complete() {
+            return;
+        }
+
+        self.complete(Err(JoinError::cancelled()));
+    }
+
+    /// Called by the task's waker when it is woken. `cancel` does not go
+    /// through here: it finishes an idle task directly.
+    pub(super) unsafe fn schedule(self) {
+        let snapshot = self.header().state.transition_to_notified_by_val();
+
+        if snapshot.is_running() {
+            // Either already queued, or the owning poll call will reschedule
+            // itself once it returns `Pending`.
+            return;
+        }
+
+        if let Some(scheduler) = *self.core().scheduler.get() {
+            // The run queue is itself an owner of the task, distinct from
+            // the task/`JoinHandle`/waker references already outstanding.
+            self.header().state.ref_inc();
+            let task = Task::from_raw(NonNull::from(self.header()));
+
+            cfg_trace! {
+                crate::task::trace::scheduled();
+            }
+
+            scheduler.as_ref().schedule(task);
+        }
+    }
+
+    /// Drop this handle's reference to the task, deallocating it if it was
+    /// the last one.
+    pub(super) unsafe fn drop_task_ref(self) {
+        if self.header().state.ref_dec() {
+            drop(Box::from_raw(self.cell.as_ptr()));
+        }
+    }
+
+    /// Drop the `JoinHandle`'s reference to the task.
+    pub(super) unsafe fn drop_join_handle(self) {
+        self.header().state.unset_join_interest();
+        self.drop_task_ref();
+    }
+
+    /// If the task has finished, write its output into `dst`. Otherwise,
+    /// stash `waker` so it is woken once the task completes.
+    pub(super) unsafe fn try_read_output(
+        self,
+        dst: *mut Poll<super::Result<T::Output>>,
+        waker: &Waker,
+    ) {
+        if self.header().state.load().is_complete() {
+            let stage = &mut *self.core().stage.get();
+            let output = match std::mem::replace(stage, Stage::Consumed) {
+                Stage::Finished(output) => output,
+                _ => unreachable!("task marked complete without a finished stage"),
+            };
+            *dst = Poll::Ready(output);
+            return;
+        }
+
+        *self.header().join_waker.get() = Some(waker.clone());
+        self.header().state.set_join_waker();
+
+        // The task may have completed concurrently, in between the check
+        // above and stashing the waker; `complete` only wakes a waker it
+        // finds already in place, so re-check here and fulfill directly if
+        // we lost that race.
+        if self.header().state.load().is_complete() {
+            if let Some(stage) = (*self.header().join_waker.get()).take() {
+                drop(stage);
+            }
+            let stage = &mut *self.core().stage.get();
+            let output = match std::mem::replace(stage, Stage::Consumed) {
+                Stage::Finished(output) => output,
+                Stage::Consumed => {
+                    // The output was already read by an earlier poll. The
+                    // waker registered above has just been taken back out,
+                    // so no waker stays registered for this `Pending`.
+                    *dst = Poll::Pending;
+                    return;
+                }
+                Stage::Running(_) => unreachable!("task marked complete while still running"),
+            };
+            *dst = Poll::Ready(output);
+            return;
+        }
+
+        *dst = Poll::Pending;
+    }
+
+    unsafe fn bind_scheduler(&self, executor: &mut dyn FnMut() -> Option<NonNull<()>>) {
+        let slot = &mut *self.core().scheduler.get();
+        if slot.is_some() {
+            return;
+        }
+
+        if let Some(ptr) = executor() {
+            let ptr: NonNull<S> = ptr.cast();
+            let task = Task::from_raw(NonNull::from(self.header()));
+            ptr.as_ref().bind(&task);
+            std::mem::forget(task);
+            *slot = Some(ptr);
+        }
+    }
+
+    /// Store the task's result, wake any parked `JoinHandle`, and release
+    /// the task's own strong reference.
+    unsafe fn complete(self, output: super::Result<T::Output>) {
+        *self.core().stage.get() = Stage::Finished(output);
+        self.header().state.transition_to_complete();
+
+        cfg_trace! {
+            crate::task::trace::completed();
+        }
+
+        if let Some(scheduler) = *self.core().scheduler.get() {
+            let task = Task::from_raw(NonNull::from(self.header()));
+            scheduler.as_ref().release_local(&task);
+            std::mem::forget(task);
+        }
+
+        if let Some(waker) = (*self.header().join_waker.get()).take() {
+            waker.wake();
+        }
+
+        if self.header().state.ref_dec() {
+            drop(Box::from_raw(self.cell.as_ptr()));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::task::raw::RawTask;
+use crate::task::JoinError;
+
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// An owned permission to join on a task (await its termination).
+///
+/// This is created by the [`task::spawn`] and [`task::spawn_blocking`]
+/// functions.
+///
+/// A `JoinHandle` *detaches* the associated task when it is dropped, which
+/// means there is no way to `join` on it after dropping a `JoinHandle`.
+/// Dropping a `JoinHandle` does not cancel the task it is associated with;
+/// use [`abort`] if the task should stop running instead.
+///
+/// [`task::spawn`]: fn.spawn.html
+/// [`task::spawn_blocking`]: fn.spawn_blocking.html
+/// [`abort`]: method@JoinHandle::abort
+pub struct JoinHandle<T> {
+    raw: RawTask,
+    _p: PhantomData<T>,
+}
+
+unsafe impl<T: Send> Send for JoinHandle<T> {}
+unsafe impl<T: Send> Sync for JoinHandle<T> {}
+
+impl<T> JoinHandle<T> {
+    pub(super) fn new(raw: RawTask) -> JoinHandle<T> {
+        JoinHandle {
+            raw,
+            _p: PhantomData,
+        }
+    }
+
+    /// Abort the task associated with this `JoinHandle`.
+    ///
+    /// Awaiting a cancelled task never completes normally. Instead, awaiting
+    /// the join handle resolves to a [`JoinError`] that is distinguishable
+    /// from a panic, once the task has actually stopped running (aborting is
+    /// not guaranteed to happen immediately: a task that is already
+    /// executing will only notice the cancellation the next time it yields
+    /// back to the runtime).
+    ///
+    /// [`JoinError`]: struct.JoinError.html
+    pub fn abort(&self) {
+        self.raw.cancel();
+    }
+
+    /// Return an [`AbortHandle`] that can be used to remotely abort this
+    /// task, without awaiting its output.
+    ///
+    /// [`AbortHandle`]: struct.AbortHandle.html
+    pub fn abort_handle(&self) -> AbortHandle {
+        self.raw.header().state.ref_inc();
+        AbortHandle { raw: self.raw }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut out = Poll::Pending;
+
+        unsafe {
+            self.raw
+                .try_read_output(&mut out as *mut _ as *mut (), cx.waker());
+        }
+
+        out
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        self.raw.drop_join_handle();
+    }
+}
+
+impl<T> fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("JoinHandle").finish()
+    }
+}
+
+/// A handle that can be used to remotely cancel a task.
+///
+/// Unlike a [`JoinHandle`], an `AbortHandle` does not carry a reference to
+/// the task's output type, can be cloned and shared with other tasks, and
+/// does not give access to awaiting task completion.
+///
+/// [`JoinHandle`]: struct.JoinHandle.html
+pub struct AbortHandle {
+    raw: RawTask,
+}
+
+unsafe impl Send for AbortHandle {}
+unsafe impl Sync for AbortHandle {}
+
+impl AbortHandle {
+    /// Abort the task associated with this handle.
+    pub fn abort(&self) {
+        self.raw.cancel();
+    }
+}
+
+impl Clone for AbortHandle {
+    fn clone(&self) -> AbortHandle {
+        self.raw.header().state.ref_inc();
+        AbortHandle { raw: self.raw }
+    }
+}
+
+impl Drop for AbortHandle {
+    fn drop(&mut self) {
+        self.raw.drop_task();
+    }
+}
+
+impl fmt::Debug for AbortHandle {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("AbortHandle").finish()
+    }
+}
@@ -0,0 +1,108 @@
+//! An intrusive, doubly-linked list of every task owned by a scheduler.
+//!
+//! Unlike `TransferStack`, membership here does not change as a task is
+//! scheduled and polled; a task stays linked in its scheduler's
+//! `OwnedList` from the moment it is bound until it is released, which is
+//! what lets a scheduler enumerate (and shut down) every task it owns.
+
+use crate::task::core::Header;
+use crate::task::Task;
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+/// A doubly-linked list of tasks, linked via `Header::owned_next`/`owned_prev`.
+pub(crate) struct OwnedList<S: 'static> {
+    head: Option<NonNull<Header>>,
+    _p: PhantomData<S>,
+}
+
+unsafe impl<S: Send + 'static> Send for OwnedList<S> {}
+unsafe impl<S: Sync + 'static> Sync for OwnedList<S> {}
+
+impl<S: 'static> OwnedList<S> {
+    pub(crate) fn new() -> OwnedList<S> {
+        OwnedList {
+            head: None,
+            _p: PhantomData,
+        }
+    }
+
+    /// Insert `task` at the head of the list.
+    pub(crate) fn insert(&mut self, task: Task<S>) {
+        let ptr = NonNull::from(task.header());
+        std::mem::forget(task);
+
+        unsafe {
+            *ptr.as_ref().owned_next.get() = self.head;
+            *ptr.as_ref().owned_prev.get() = None;
+
+            if let Some(head) = self.head {
+                *head.as_ref().owned_prev.get() = Some(ptr);
+            }
+        }
+
+        self.head = Some(ptr);
+    }
+
+    /// Remove the task identified by `header` from the list.
+    ///
+    /// # Safety
+    ///
+    /// `header` must currently be linked into this list.
+    pub(crate) unsafe fn remove(&mut self, header: NonNull<Header>) -> Task<S> {
+        let prev = *header.as_ref().owned_prev.get();
+        let next = *header.as_ref().owned_next.get();
+
+        match prev {
+            Some(prev) => *prev.as_ref().owned_next.get() = next,
+            None => self.head = next,
+        }
+
+        if let Some(next) = next {
+            *next.as_ref().owned_prev.get() = prev;
+        }
+
+        Task::from_raw(header)
+    }
+
+    /// Iterate every task currently in the list without removing any of
+    /// them, in no particular order.
+    ///
+    /// Unlike `drain`, this does not take ownership of the tasks it yields:
+    /// it is only safe to use for inspecting which tasks are linked, e.g. to
+    /// collect their headers before shutting each of them down individually.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = NonNull<Header>> + '_ {
+        struct Iter<'a>(Option<NonNull<Header>>, PhantomData<&'a ()>);
+
+        impl<'a> Iterator for Iter<'a> {
+            type Item = NonNull<Header>;
+
+            fn next(&mut self) -> Option<NonNull<Header>> {
+                let curr = self.0?;
+                self.0 = unsafe { *curr.as_ref().owned_next.get() };
+                Some(curr)
+            }
+        }
+
+        Iter(self.head, PhantomData)
+    }
+
+    /// Drain every task out of the list, in no particular order.
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = Task<S>> {
+        struct Iter<S: 'static>(Option<NonNull<Header>>, PhantomData<S>);
+
+        impl<S: 'static> Iterator for Iter<S> {
+            type Item = Task<S>;
+
+            fn next(&mut self) -> Option<Task<S>> {
+                let curr = self.0?;
+                self.0 = unsafe { *curr.as_ref().owned_next.get() };
+                Some(unsafe { Task::from_raw(curr) })
+            }
+        }
+
+        let head = self.head.take();
+        Iter(head, PhantomData)
+    }
+}
@@ -0,0 +1,357 @@
+//! A single-threaded task set, for running `!Send` futures.
+
+use crate::task::{self, Header, JoinHandle, OwnedList, Schedule, Task, TransferStack};
+
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+thread_local! {
+    static CURRENT: RefCell<Option<Rc<Shared>>> = RefCell::new(None);
+}
+
+/// A set of tasks which are executed on the same thread.
+///
+/// In some cases, it is necessary to run one or more futures that do not
+/// implement [`Send`] and thus are unsafe to send between threads. A
+/// `LocalSet` provides a way to spawn such `!Send` futures as Tokio tasks,
+/// by ensuring that they will only ever be polled from the thread that
+/// called [`run_until`] or [`block_on`].
+///
+/// A `LocalSet` does *not* run on its own: tasks spawned on it only make
+/// progress while the set is being driven by [`run_until`] or [`block_on`].
+///
+/// [`Send`]: https://doc.rust-lang.org/std/marker/trait.Send.html
+/// [`run_until`]: method@LocalSet::run_until
+/// [`block_on`]: method@LocalSet::block_on
+pub struct LocalSet {
+    shared: Rc<Shared>,
+}
+
+/// Spawns a `!Send` future onto the current [`LocalSet`].
+///
+/// The provided future is queued on the current `LocalSet` and starts
+/// running the next time that set polls its tasks, even if the returned
+/// `JoinHandle` is never awaited.
+///
+/// # Panics
+///
+/// Panics if called outside the context of a [`LocalSet`] (i.e. from outside
+/// a future passed to [`LocalSet::run_until`] or [`LocalSet::block_on`]).
+///
+/// [`LocalSet`]: struct.LocalSet.html
+/// [`LocalSet::run_until`]: method@LocalSet::run_until
+/// [`LocalSet::block_on`]: method@LocalSet::block_on
+pub fn spawn_local<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+    F::Output: 'static,
+{
+    CURRENT.with(|current| {
+        let shared = current
+            .borrow()
+            .clone()
+            .expect("`spawn_local` called from outside of a `LocalSet`");
+
+        shared.spawn(future)
+    })
+}
+
+/// The `Schedule` implementation backing a `LocalSet`.
+///
+/// `Shared` itself is not `Send`/`Sync`, but `Task<Shared>` requires its
+/// scheduler to be. That bound is upheld by construction, not by the type
+/// system: a task bound to a `Shared` is only ever polled, bound, or
+/// released from the thread that owns the enclosing `LocalSet`, because
+/// `LocalSet` itself is `!Send` and tasks spawned through it are never
+/// handed anywhere else.
+///
+/// `schedule`, on the other hand, is reachable from *any* thread: it is
+/// called by a task's `Waker`, which a spawned future is free to clone and
+/// fire from another thread (e.g. from a channel or timer callback). `queue`
+/// is therefore a lock-free `TransferStack` rather than a `RefCell`, so a
+/// remote wakeup can never race with this thread's own `tick`.
+struct Shared {
+    /// Tasks ready to be polled. Pushed to from any thread (via `schedule`),
+    /// drained only by the thread driving this `LocalSet`.
+    queue: TransferStack<Shared>,
+
+    /// Every task this `LocalSet` owns, whether or not it is currently
+    /// queued to run. Only ever touched from the owning thread, so a
+    /// `RefCell` is sufficient.
+    owned: RefCell<OwnedList<Shared>>,
+
+    /// Set whenever a task is scheduled, so `block_on` can tell whether it
+    /// needs to park again without using an `Acquire` on the queue itself.
+    woken: AtomicBool,
+
+    /// The thread currently parked in `block_on`, if any. `schedule` unparks
+    /// it so a remotely-woken `spawn_local`'d task is never left waiting for
+    /// a wakeup that already happened.
+    parker: Mutex<Option<std::thread::Thread>>,
+}
+
+unsafe impl Send for Shared {}
+unsafe impl Sync for Shared {}
+
+/// Wraps a `!Send` future so it can be stored in a task whose `Task<S>`
+/// handle requires `Send`.
+///
+/// # Safety
+///
+/// Same invariant as `Shared`: a `LocalFuture` is only ever polled or
+/// dropped on the thread that spawned it.
+struct LocalFuture<F>(F);
+
+unsafe impl<F> Send for LocalFuture<F> {}
+
+impl<F: Future> Future for LocalFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        unsafe { self.map_unchecked_mut(|me| &mut me.0) }.poll(cx)
+    }
+}
+
+impl Shared {
+    fn new() -> Rc<Shared> {
+        Rc::new(Shared {
+            queue: TransferStack::new(),
+            owned: RefCell::new(OwnedList::new()),
+            woken: AtomicBool::new(false),
+            parker: Mutex::new(None),
+        })
+    }
+
+    fn spawn<F>(self: &Rc<Self>, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        let (task, join) = task::joinable::<_, Shared>(LocalFuture(future));
+        self.queue.push(task);
+        join
+    }
+
+    /// Run every task currently queued exactly once. Returns `true` if any
+    /// task was polled.
+    fn tick(self: &Rc<Self>) -> bool {
+        let mut any = false;
+
+        // `self` is kept alive by the `Rc` that `LocalSet` holds for at
+        // least as long as any task bound to it might run, so handing out a
+        // raw pointer to `*self` here is sound: it is never dereferenced
+        // after the `Shared` allocation it points into has been freed.
+        let ptr = NonNull::from(&**self);
+
+        for task in self.queue.drain() {
+            any = true;
+
+            if let Some(task) = task.run(|| Some(ptr.cast())) {
+                self.queue.push(task);
+            }
+        }
+
+        any
+    }
+}
+
+impl Schedule for Shared {
+    fn bind(&self, task: &Task<Self>) {
+        let ptr = NonNull::from(task.header());
+        task.header().state.ref_inc();
+        self.owned.borrow_mut().insert(unsafe { Task::from_raw(ptr) });
+    }
+
+    fn release(&self, _task: Task<Self>) {}
+
+    fn release_local(&self, task: &Task<Self>) {
+        let ptr = NonNull::from(task.header());
+        drop(unsafe { self.owned.borrow_mut().remove(ptr) });
+    }
+
+    fn schedule(&self, task: Task<Self>) {
+        self.queue.push(task);
+        self.woken.store(true, Ordering::Release);
+
+        if let Some(thread) = &*self.parker.lock().unwrap() {
+            thread.unpark();
+        }
+    }
+}
+
+impl LocalSet {
+    /// Create a new `LocalSet`.
+    pub fn new() -> LocalSet {
+        LocalSet {
+            shared: Shared::new(),
+        }
+    }
+
+    /// Spawn a `!Send` future onto this `LocalSet`.
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + 'static,
+        F::Output: 'static,
+    {
+        self.shared.spawn(future)
+    }
+
+    /// Run `future` to completion, making `task::spawn_local` available to
+    /// it (and to anything it spawns), and polling this set's queued tasks
+    /// whenever `future` itself is not making progress.
+    pub async fn run_until<F: Future>(&self, future: F) -> F::Output {
+        RunUntil {
+            shared: self.shared.clone(),
+            future,
+        }
+        .await
+    }
+
+    /// Block the current thread until `future` completes, running this
+    /// `LocalSet`'s tasks (including any spawned via `spawn_local` while
+    /// `future` runs) in between.
+    ///
+    /// Unlike `run_until`, this does not require an enclosing Tokio runtime;
+    /// it parks the current thread directly.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let waker = parking_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+
+        // Save whatever was there before rather than unconditionally
+        // clearing it on the way out: this `block_on` may itself be running
+        // inside a future driven by an outer `LocalSet` on this thread (e.g.
+        // an async fn that owns and drives a nested `LocalSet`), and that
+        // outer call is still active once this one returns.
+        let previous =
+            CURRENT.with(|current| current.borrow_mut().replace(self.shared.clone()));
+        *self.shared.parker.lock().unwrap() = Some(std::thread::current());
+
+        let result = loop {
+            if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+                break out;
+            }
+
+            while self.shared.tick() {}
+
+            // `woken` is set by `Shared::schedule` (possibly from another
+            // thread) whenever a locally-spawned task is scheduled; consume
+            // it here rather than re-checking the queue so a wakeup that
+            // raced in between `tick` and this check is not lost. If it
+            // raced in after this check too, `schedule`'s `Thread::unpark`
+            // call still deposits a wakeup token for the `park` below, so
+            // this can never hang.
+            if !self.shared.woken.swap(false, Ordering::AcqRel) {
+                std::thread::park();
+            }
+        };
+
+        *self.shared.parker.lock().unwrap() = None;
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+
+        result
+    }
+}
+
+impl Default for LocalSet {
+    fn default() -> LocalSet {
+        LocalSet::new()
+    }
+}
+
+impl Drop for LocalSet {
+    fn drop(&mut self) {
+        // Shut down whatever is still queued first; a task that is bound to
+        // `self.shared` detaches itself from `owned` as a side effect of
+        // completing (see `release_local`), so it can never be processed
+        // twice by the second loop below.
+        for task in self.shared.queue.drain() {
+            task.shutdown();
+        }
+
+        // Anything left in `owned` is currently parked on some external
+        // wakeup (e.g. a channel or timer) rather than queued, so the loop
+        // above never touched it. Left alone, its stored `Shared` pointer
+        // would dangle once this `Rc` is dropped and the next wakeup would
+        // dereference freed memory; shut each of these down directly
+        // instead. Collect the headers first: `shutdown` below removes each
+        // one from `owned` via `release_local`, which would conflict with
+        // still iterating over it.
+        let parked: Vec<NonNull<Header>> = self.shared.owned.borrow().iter().collect();
+
+        for header in parked {
+            unsafe { Task::<Shared>::from_raw(header) }.shutdown();
+        }
+    }
+}
+
+impl std::fmt::Debug for LocalSet {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("LocalSet").finish()
+    }
+}
+
+struct RunUntil<F> {
+    shared: Rc<Shared>,
+    future: F,
+}
+
+impl<F: Future> Future for RunUntil<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let me = unsafe { self.get_unchecked_mut() };
+
+        // Save/restore rather than unconditionally clearing on the way out:
+        // this poll call may itself be nested inside an outer `LocalSet`'s
+        // `run_until`/`block_on` on this thread, which is still active once
+        // this call returns.
+        let previous = CURRENT.with(|current| current.borrow_mut().replace(me.shared.clone()));
+
+        let result = unsafe { Pin::new_unchecked(&mut me.future) }.poll(cx);
+
+        while me.shared.tick() {}
+
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+
+        result
+    }
+}
+
+/// A `Waker` that unparks the thread it was created on.
+fn parking_waker() -> Waker {
+    use std::task::{RawWaker, RawWakerVTable};
+
+    unsafe fn clone(ptr: *const ()) -> RawWaker {
+        let arc = Arc::from_raw(ptr as *const std::thread::Thread);
+        std::mem::forget(arc.clone());
+        RawWaker::new(Arc::into_raw(arc) as *const (), &VTABLE)
+    }
+
+    unsafe fn wake(ptr: *const ()) {
+        let arc = Arc::from_raw(ptr as *const std::thread::Thread);
+        arc.unpark();
+    }
+
+    unsafe fn wake_by_ref(ptr: *const ()) {
+        let arc = Arc::from_raw(ptr as *const std::thread::Thread);
+        arc.unpark();
+        std::mem::forget(arc);
+    }
+
+    unsafe fn drop_waker(ptr: *const ()) {
+        drop(Arc::from_raw(ptr as *const std::thread::Thread))
+    }
+
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_waker);
+
+    let thread = Arc::new(std::thread::current());
+    let raw = RawWaker::new(Arc::into_raw(thread) as *const (), &VTABLE);
+    unsafe { Waker::from_raw(raw) }
+}
@@ -95,11 +95,19 @@
 //! `spawn`, `JoinHandle`, and `JoinError` are present when the "rt-core"
 //! feature flag is enabled.
 //!
+//! `task::spawn` requires the spawned future to be `Send`, since it may run
+//! on any worker thread the runtime chooses. For futures built on `!Send`
+//! state (e.g. `Rc`, `RefCell`), use [`task::spawn_local`] together with a
+//! [`LocalSet`] instead, which guarantees they only ever run on the thread
+//! that drives the `LocalSet`.
+//!
 //! [`task::spawn`]: fn.spawn.html
 //! [thread_spawn]: https://doc.rust-lang.org/std/thread/fn.spawn.html
 //! [`JoinHandle`]: struct.JoinHandle.html
 //! [thread_join]: https://doc.rust-lang.org/std/thread/struct.JoinHandle.html
 //! [`JoinError`]: struct.JoinError.html
+//! [`task::spawn_local`]: fn.spawn_local.html
+//! [`LocalSet`]: struct.LocalSet.html
 //!
 //! ### Blocking and Yielding
 //!
@@ -170,6 +178,28 @@
 //! [`task::spawn_blocking`]: fn.spawn_blocking.html
 //! [`task::block_in_place`]: fn.block_in_place.html
 //! [rt-threaded]: ../runtime/struct.Builder.html#method.threaded_scheduler
+//!
+//! ### Cancellation
+//!
+//! Spawned tasks can be forcefully cancelled with [`JoinHandle::abort`], or
+//! with the cloneable [`AbortHandle`] returned by
+//! [`JoinHandle::abort_handle`]. This does not require cooperation from the
+//! task: once cancelled, the next time the task would otherwise be polled,
+//! it is dropped instead and its `JoinHandle` resolves to a [`JoinError`]
+//! that is distinguishable from a panic.
+//!
+//! [`JoinHandle::abort`]: struct.JoinHandle.html#method.abort
+//! [`JoinHandle::abort_handle`]: struct.JoinHandle.html#method.abort_handle
+//! [`AbortHandle`]: struct.AbortHandle.html
+//!
+//! ### Instrumentation
+//!
+//! When built with the `tracing` feature, every task is given its own
+//! `tracing` span, entered for the duration of each poll, and spawn,
+//! schedule, poll and completion events are emitted under the
+//! `tokio::task` target. This lets a `tracing` subscriber observe poll
+//! durations and scheduling latency without any changes to application
+//! code.
 cfg_blocking! {
     mod blocking;
     pub use blocking::spawn_blocking;
@@ -192,7 +222,12 @@ use self::harness::Harness;
 cfg_rt_core! {
     mod join;
     #[allow(unreachable_pub)] // https://github.com/rust-lang/rust/issues/57411
-    pub use self::join::JoinHandle;
+    pub use self::join::{AbortHandle, JoinHandle};
+}
+
+cfg_rt_core! {
+    mod local;
+    pub use self::local::{spawn_local, LocalSet};
 }
 
 mod list;
@@ -212,6 +247,10 @@ pub(crate) use self::stack::TransferStack;
 mod state;
 use self::state::{Snapshot, State};
 
+cfg_trace! {
+    mod trace;
+}
+
 mod waker;
 
 mod yield_now;
@@ -261,6 +300,10 @@ cfg_rt_threaded! {
         T: Future + Send + 'static,
         S: Schedule,
     {
+        cfg_trace! {
+            trace::spawned();
+        }
+
         Task {
             raw: RawTask::new_background::<_, S>(task),
             _p: PhantomData,
@@ -276,6 +319,10 @@ where
 {
     let raw = RawTask::new_joinable::<_, S>(task);
 
+    cfg_trace! {
+        trace::spawned();
+    }
+
     let task = Task {
         raw,
         _p: PhantomData,
@@ -311,10 +358,21 @@ impl<S: Schedule> Task<S> {
     where
         F: FnMut() -> Option<NonNull<S>>,
     {
-        if unsafe {
+        cfg_trace! {
+            let _enter = self.header().span.enter();
+            trace::poll_start();
+        }
+
+        let needs_reschedule = unsafe {
             self.raw
                 .poll(&mut || executor().map(|ptr| ptr.cast::<()>()))
-        } {
+        };
+
+        cfg_trace! {
+            trace::poll_end();
+        }
+
+        if needs_reschedule {
             Some(self)
         } else {
             // Cleaning up the `Task` instance is done from within the poll
@@ -0,0 +1,202 @@
+use crate::task::core::{Cell, Header};
+use crate::task::harness::Harness;
+use crate::task::state::State;
+use crate::task::Schedule;
+
+use std::future::Future;
+use std::ptr::NonNull;
+use std::task::Waker;
+
+/// A type-erased, raw pointer to a task allocation.
+///
+/// `RawTask` knows how to reach the `Vtable` stored in the task's `Header`
+/// and goes through it for every operation, which is what lets `Task<S>` be
+/// generic only over the scheduler and not over the future it is running.
+#[derive(Clone, Copy)]
+pub(super) struct RawTask {
+    ptr: NonNull<Header>,
+}
+
+/// The table of functions used to interact with a task, specialized for a
+/// particular future `T` and scheduler `S`.
+pub(super) struct Vtable {
+    /// Polls the task, returning `true` if it should be immediately
+    /// rescheduled.
+    pub(super) poll: unsafe fn(NonNull<Header>, &mut dyn FnMut() -> Option<NonNull<()>>) -> bool,
+
+    /// Drop this task's reference, deallocating the task if it was the last
+    /// one.
+    pub(super) drop_task: unsafe fn(NonNull<Header>),
+
+    /// Transition the task to cancelled. An idle task is finished right
+    /// away; a running task notices the cancellation when its current poll
+    /// returns.
+    pub(super) cancel: unsafe fn(NonNull<Header>),
+
+    /// Force the task to complete as cancelled right away. Only valid when
+    /// the caller knows the task cannot be concurrently polling (e.g. it was
+    /// just pulled off a run queue that nothing else is driving).
+    pub(super) shutdown: unsafe fn(NonNull<Header>),
+
+    /// If the task has finished, write its output (type-erased as `*mut
+    /// Poll<Result<T::Output, JoinError>>`) and return. Otherwise, stash
+    /// `waker` to be woken on completion.
+    pub(super) try_read_output: unsafe fn(NonNull<Header>, *mut (), &Waker),
+
+    /// Drop the `JoinHandle`'s reference to this allocation.
+    pub(super) drop_join_handle: unsafe fn(NonNull<Header>),
+}
+
+impl RawTask {
+    pub(super) fn new_joinable<T, S>(task: T) -> RawTask
+    where
+        T: Future + Send + 'static,
+        S: Schedule,
+    {
+        let ptr = Cell::<T, S>::new(task, State::new_joinable(), vtable::<T, S>());
+        RawTask {
+            ptr: NonNull::from(Box::leak(ptr)).cast(),
+        }
+    }
+
+    pub(super) fn new_background<T, S>(task: T) -> RawTask
+    where
+        T: Future + Send + 'static,
+        S: Schedule,
+    {
+        let ptr = Cell::<T, S>::new(task, State::new_background(), vtable::<T, S>());
+        RawTask {
+            ptr: NonNull::from(Box::leak(ptr)).cast(),
+        }
+    }
+
+    /// Create a `RawTask` from a raw, type-erased pointer previously
+    /// produced by `into_raw`.
+    pub(super) unsafe fn from_raw(ptr: NonNull<Header>) -> RawTask {
+        RawTask { ptr }
+    }
+
+    pub(super) fn into_raw(self) -> NonNull<Header> {
+        self.ptr
+    }
+
+    pub(super) fn header(&self) -> &Header {
+        unsafe { self.ptr.as_ref() }
+    }
+
+    /// Poll the task.
+    ///
+    /// Returns `true` if the task needs to be immediately rescheduled.
+    pub(super) unsafe fn poll(self, executor: &mut dyn FnMut() -> Option<NonNull<()>>) -> bool {
+        let vtable = self.header().vtable;
+        (vtable.poll)(self.ptr, executor)
+    }
+
+    /// Drop the `Task` handle's reference to this allocation.
+    pub(super) fn drop_task(self) {
+        let vtable = self.header().vtable;
+        unsafe { (vtable.drop_task)(self.ptr) }
+    }
+
+    /// Cancel the task as part of runtime shutdown. Unlike `JoinHandle::abort`
+    /// this runs synchronously on the thread that owns the run queue, since
+    /// at shutdown no other thread can be concurrently polling the task, and
+    /// it always finishes the task immediately rather than waiting for a
+    /// poll that will never come.
+    pub(super) fn cancel_from_queue(self) {
+        let vtable = self.header().vtable;
+        unsafe { (vtable.shutdown)(self.ptr) }
+    }
+
+    /// Cancel the task from any thread, e.g. in response to
+    /// `JoinHandle::abort`.
+    pub(super) fn cancel(self) {
+        let vtable = self.header().vtable;
+        unsafe { (vtable.cancel)(self.ptr) }
+    }
+
+    /// Read the task's output if it has finished, otherwise stash `waker`.
+    ///
+    /// # Safety
+    ///
+    /// `dst` must point to a valid, initialized `Poll<Result<T::Output,
+    /// JoinError>>`, where `T` is the same future type the task was spawned
+    /// with.
+    pub(super) unsafe fn try_read_output(self, dst: *mut (), waker: &Waker) {
+        let vtable = self.header().vtable;
+        (vtable.try_read_output)(self.ptr, dst, waker)
+    }
+
+    /// Drop the `JoinHandle`'s reference to this allocation.
+    pub(super) fn drop_join_handle(self) {
+        let vtable = self.header().vtable;
+        unsafe { (vtable.drop_join_handle)(self.ptr) }
+    }
+}
+
+fn vtable<T, S>() -> &'static Vtable
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    &Vtable {
+        poll: poll::<T, S>,
+        drop_task: drop_task::<T, S>,
+        cancel: cancel::<T, S>,
+        shutdown: shutdown::<T, S>,
+        try_read_output: try_read_output::<T, S>,
+        drop_join_handle: drop_join_handle::<T, S>,
+    }
+}
+
+unsafe fn poll<T, S>(
+    ptr: NonNull<Header>,
+    executor: &mut dyn FnMut() -> Option<NonNull<()>>,
+) -> bool
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    Harness::<T, S>::from_raw(ptr).poll(executor)
+}
+
+unsafe fn drop_task<T, S>(ptr: NonNull<Header>)
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    Harness::<T, S>::from_raw(ptr).drop_task_ref()
+}
+
+unsafe fn cancel<T, S>(ptr: NonNull<Header>)
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    Harness::<T, S>::from_raw(ptr).cancel()
+}
+
+unsafe fn shutdown<T, S>(ptr: NonNull<Header>)
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    Harness::<T, S>::from_raw(ptr).shutdown()
+}
+
+unsafe fn try_read_output<T, S>(ptr: NonNull<Header>, dst: *mut (), waker: &Waker)
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    let dst = dst as *mut std::task::Poll<super::Result<T::Output>>;
+    Harness::<T, S>::from_raw(ptr).try_read_output(dst, waker)
+}
+
+unsafe fn drop_join_handle<T, S>(ptr: NonNull<Header>)
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    Harness::<T, S>::from_raw(ptr).drop_join_handle()
+}
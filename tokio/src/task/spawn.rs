@@ -0,0 +1,53 @@
+use crate::runtime::context;
+use crate::task::JoinHandle;
+
+use std::future::Future;
+
+/// Spawns a new asynchronous task, returning a [`JoinHandle`] for it.
+///
+/// Spawning a task enables the task to execute concurrently to other tasks.
+/// The spawned task may execute on the current thread, or it may be sent to
+/// a different thread to be executed. The specifics depend on the current
+/// [`Runtime`] configuration.
+///
+/// There is no guarantee that a spawned task will execute to completion. All
+/// tasks are executed on the runtime when its [`run`] method is called.
+///
+/// Once a task finishes, it keeps its output accessible via its
+/// [`JoinHandle`] until the handle is dropped or its output is awaited.
+/// Dropping the handle instead detaches the task; it keeps running with its
+/// output discarded. Use [`JoinHandle::abort`] to cancel a spawned task
+/// instead of detaching it.
+///
+/// [`Runtime`]: ../runtime/struct.Runtime.html
+/// [`run`]: ../runtime/struct.Runtime.html#method.run
+/// [`JoinHandle`]: struct.JoinHandle.html
+/// [`JoinHandle::abort`]: method@JoinHandle::abort
+///
+/// # Panics
+///
+/// Panics if called from **outside** of a Tokio runtime.
+///
+/// # Examples
+///
+/// ```
+/// use tokio::task;
+///
+/// # async fn docs() {
+/// let join = task::spawn(async {
+///     "hello world!"
+/// });
+///
+/// let result = join.await.unwrap();
+/// assert_eq!(result, "hello world!");
+/// # }
+/// ```
+pub fn spawn<T>(task: T) -> JoinHandle<T::Output>
+where
+    T: Future + Send + 'static,
+    T::Output: Send + 'static,
+{
+    let spawn_handle = context::spawn_handle()
+        .expect("must be called from the context of a Tokio 1.x runtime");
+    spawn_handle.spawn(task)
+}
@@ -0,0 +1,69 @@
+//! An intrusive, lock-free stack used to hand tasks from any thread to the
+//! scheduler's run queue (e.g. when a task is notified from a remote
+//! thread).
+
+use crate::task::core::Header;
+use crate::task::Task;
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed};
+
+/// A lock-free, intrusive, LIFO stack of tasks, linked via
+/// `Header::queue_next`.
+pub(crate) struct TransferStack<S: 'static> {
+    head: AtomicPtr<Header>,
+    _p: PhantomData<S>,
+}
+
+impl<S: 'static> TransferStack<S> {
+    pub(crate) fn new() -> TransferStack<S> {
+        TransferStack {
+            head: AtomicPtr::new(std::ptr::null_mut()),
+            _p: PhantomData,
+        }
+    }
+
+    /// Push `task` onto the stack.
+    pub(crate) fn push(&self, task: Task<S>) {
+        let ptr = task.header() as *const _ as *mut Header;
+        // The stack now owns the reference that `task` held.
+        std::mem::forget(task);
+
+        let mut curr = self.head.load(Relaxed);
+
+        loop {
+            unsafe {
+                *(*ptr).queue_next.get() = NonNull::new(curr);
+            }
+
+            match self
+                .head
+                .compare_exchange_weak(curr, ptr, AcqRel, Acquire)
+            {
+                Ok(_) => return,
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+
+    /// Take every task currently on the stack, in LIFO order.
+    pub(crate) fn drain(&self) -> impl Iterator<Item = Task<S>> {
+        struct Iter<S: 'static>(Option<NonNull<Header>>, PhantomData<S>);
+
+        impl<S: 'static> Iterator for Iter<S> {
+            type Item = Task<S>;
+
+            fn next(&mut self) -> Option<Task<S>> {
+                let curr = self.0?;
+                let task = unsafe { Task::<S>::from_raw(curr) };
+                self.0 = unsafe { *task.header().queue_next.get() };
+                Some(task)
+            }
+        }
+
+        let ptr = self.head.swap(std::ptr::null_mut(), AcqRel);
+        Iter(NonNull::new(ptr), PhantomData)
+    }
+}
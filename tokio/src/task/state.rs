@@ -0,0 +1,252 @@
+use std::fmt;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering::{AcqRel, Acquire};
+use std::usize;
+
+/// The state of a task, stored in a single atomic `usize`.
+///
+/// The current state value is split into a number of bits that track the
+/// lifecycle of the task plus the reference count for the task's handles
+/// (the task itself, the `JoinHandle`, any `AbortHandle`s and wakers).
+///
+/// ```text
+/// |   ref count   |   lifecycle bits   |
+/// |----------------|--------------------|
+///                    6 bits
+/// ```
+pub(super) struct State {
+    val: AtomicUsize,
+}
+
+/// A snapshot of the state, taken at a single point in time.
+#[derive(Copy, Clone)]
+pub(super) struct Snapshot(usize);
+
+/// Task is running.
+const RUNNING: usize = 0b0_0000_1;
+
+/// Task has finished executing (either successfully or as the result of a
+/// panic or cancellation).
+const COMPLETE: usize = 0b0_0001_0;
+
+/// A notification was received while the task was already running.
+const NOTIFIED: usize = 0b0_0010_0;
+
+/// Task has been cancelled and should shut down at the next opportunity.
+///
+/// This is set by `JoinHandle::abort`/`AbortHandle::abort` and is checked
+/// before every poll and again after a poll returns `Pending`. A task that
+/// is idle when aborted is finished right away by the aborting caller, so
+/// it is aborted even if it never gets a chance to run again.
+const CANCELLED: usize = 0b0_0100_0;
+
+/// Is there a join handle?
+const JOIN_INTEREST: usize = 0b0_1000_0;
+
+/// A waker for the join handle has been set.
+const JOIN_WAKER: usize = 0b1_0000_0;
+
+/// The task's lifecycle is tracked by the low 6 bits; the rest of the `usize`
+/// is used as a reference count.
+const LIFECYCLE_MASK: usize = 0b11_1111;
+const REF_ONE: usize = LIFECYCLE_MASK + 1;
+const REF_COUNT_MASK: usize = !LIFECYCLE_MASK;
+
+/// The number of lifecycle bits below the reference count, i.e. how far the
+/// count is shifted left within the `usize`.
+const REF_COUNT_SHIFT: u32 = LIFECYCLE_MASK.count_ones();
+const MAX_REFCOUNT: usize = usize::MAX >> REF_COUNT_SHIFT;
+
+/// Both the task and the `JoinHandle` hold a reference when the task is
+/// spawned with `joinable`, so the initial state starts with a ref count of
+/// two.
+const INITIAL_STATE_JOINABLE: usize = (REF_ONE * 2) | RUNNING | JOIN_INTEREST;
+
+/// A task spawned without a join handle only has the single task reference.
+const INITIAL_STATE_BACKGROUND: usize = REF_ONE | RUNNING;
+
+impl State {
+    /// Create a new `State` for a task that has a `JoinHandle`.
+    pub(super) fn new_joinable() -> State {
+        State {
+            val: AtomicUsize::new(INITIAL_STATE_JOINABLE),
+        }
+    }
+
+    /// Create a new `State` for a task spawned without a `JoinHandle`.
+    pub(super) fn new_background() -> State {
+        State {
+            val: AtomicUsize::new(INITIAL_STATE_BACKGROUND),
+        }
+    }
+
+    /// Load the current snapshot.
+    pub(super) fn load(&self) -> Snapshot {
+        Snapshot(self.val.load(Acquire))
+    }
+
+    /// Transition a task from `Running` to `Complete`.
+    pub(super) fn transition_to_complete(&self) -> Snapshot {
+        Snapshot(self.fetch_update(|curr| (curr & !RUNNING) | COMPLETE))
+    }
+
+    /// Transition the task to the cancelled state.
+    ///
+    /// If the task is not currently running, this also transitions it
+    /// straight to `COMPLETE` in the same compare-and-swap, so that exactly
+    /// one of however many callers race to cancel the same idle task is the
+    /// one whose `fetch_update` performs that not-complete -> complete
+    /// transition. That caller (and only that caller) sees a returned
+    /// snapshot with neither bit set and is the one responsible for actually
+    /// finishing the task; every other racing caller's own CAS can only
+    /// succeed once `COMPLETE` is already set, so its snapshot already shows
+    /// `is_complete()`.
+    ///
+    /// Returns the snapshot taken *before* the transition so the caller can
+    /// tell whether the task was already complete (in which case cancelling
+    /// is a no-op) and whether it was running (in which case the in-flight
+    /// poll notices the cancellation) or idle (in which case the caller
+    /// finishes it).
+    pub(super) fn transition_to_cancelled(&self) -> Snapshot {
+        Snapshot(self.fetch_update(|curr| {
+            let curr = curr | CANCELLED;
+            if curr & RUNNING == RUNNING {
+                curr
+            } else {
+                curr | COMPLETE
+            }
+        }))
+    }
+
+    /// Clear the `running` and `notified` bits, as the task is about to
+    /// return `Pending` to its scheduler.
+    ///
+    /// Returns the snapshot from *before* the transition, so the caller can
+    /// tell whether a wakeup raced in while the task was still polling (in
+    /// which case it must be rescheduled immediately rather than waiting for
+    /// a future wakeup that already happened).
+    pub(super) fn transition_to_idle(&self) -> Snapshot {
+        Snapshot(self.fetch_update(|curr| curr & !RUNNING & !NOTIFIED))
+    }
+
+    /// Mark the task as notified, scheduling it if it is currently idle.
+    ///
+    /// Returns the snapshot from *before* the transition. If it was already
+    /// `running`, the caller does not need to push the task onto the
+    /// scheduler's run queue: either it is already queued, or the in-flight
+    /// poll will notice `NOTIFIED` when it next returns `Pending`.
+    pub(super) fn transition_to_notified_by_val(&self) -> Snapshot {
+        Snapshot(self.fetch_update(|curr| {
+            if curr & RUNNING == RUNNING {
+                curr | NOTIFIED
+            } else {
+                curr | RUNNING | NOTIFIED
+            }
+        }))
+    }
+
+    /// Set the `join interest` bit to false.
+    pub(super) fn unset_join_interest(&self) -> Snapshot {
+        Snapshot(self.fetch_update(|curr| curr & !JOIN_INTEREST))
+    }
+
+    /// Set the `join waker` bit, indicating a waker has been stashed in the
+    /// task for the `JoinHandle`.
+    pub(super) fn set_join_waker(&self) -> Snapshot {
+        Snapshot(self.fetch_update(|curr| curr | JOIN_WAKER))
+    }
+
+    /// Increment the reference count.
+    pub(super) fn ref_inc(&self) {
+        let prev = self.val.fetch_add(REF_ONE, AcqRel);
+
+        // See `std::sync::Arc` for why this is necessary.
+        if prev > MAX_REFCOUNT {
+            std::process::abort();
+        }
+    }
+
+    /// Decrement the reference count, returning `true` if this was the final
+    /// reference.
+    pub(super) fn ref_dec(&self) -> bool {
+        let prev = self.val.fetch_sub(REF_ONE, AcqRel);
+        Snapshot(prev).ref_count() == 1
+    }
+
+    /// Apply a state transformation, retrying on contention, and return the
+    /// snapshot *before* the transformation was applied.
+    fn fetch_update(&self, mut f: impl FnMut(usize) -> usize) -> usize {
+        let mut curr = self.val.load(Acquire);
+
+        loop {
+            let next = f(curr);
+
+            match self
+                .val
+                .compare_exchange_weak(curr, next, AcqRel, Acquire)
+            {
+                Ok(_) => return curr,
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+}
+
+impl Snapshot {
+    /// Returns `true` if the task is currently running.
+    pub(super) fn is_running(self) -> bool {
+        self.0 & RUNNING == RUNNING
+    }
+
+    /// Returns `true` if the task has completed execution.
+    pub(super) fn is_complete(self) -> bool {
+        self.0 & COMPLETE == COMPLETE
+    }
+
+    /// Returns `true` if the task has been notified for polling.
+    pub(super) fn is_notified(self) -> bool {
+        self.0 & NOTIFIED == NOTIFIED
+    }
+
+    /// Returns `true` if the task has been cancelled, whether or not it has
+    /// finished unwinding yet.
+    pub(super) fn is_cancelled(self) -> bool {
+        self.0 & CANCELLED == CANCELLED
+    }
+
+    /// Returns `true` if there is a live `JoinHandle`. An `AbortHandle`
+    /// does not count: it only holds a reference.
+    pub(super) fn is_join_interested(self) -> bool {
+        self.0 & JOIN_INTEREST == JOIN_INTEREST
+    }
+
+    /// Returns `true` if a waker has been stashed for the `JoinHandle`.
+    pub(super) fn has_join_waker(self) -> bool {
+        self.0 & JOIN_WAKER == JOIN_WAKER
+    }
+
+    /// Returns the number of outstanding references to the task.
+    pub(super) fn ref_count(self) -> usize {
+        (self.0 & REF_COUNT_MASK) >> REF_COUNT_SHIFT
+    }
+}
+
+impl fmt::Debug for State {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.load().fmt(fmt)
+    }
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Snapshot")
+            .field("running", &self.is_running())
+            .field("complete", &self.is_complete())
+            .field("notified", &self.is_notified())
+            .field("cancelled", &self.is_cancelled())
+            .field("join_interested", &self.is_join_interested())
+            .field("join_waker", &self.has_join_waker())
+            .field("ref_count", &self.ref_count())
+            .finish()
+    }
+}
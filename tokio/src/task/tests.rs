@@ -0,0 +1,182 @@
+use super::*;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// A `Schedule` used by tests that never actually go through a run queue
+/// (the task's own reference-count bookkeeping is what's under test, not a
+/// scheduler).
+struct NoopSchedule;
+
+impl Schedule for NoopSchedule {
+    fn bind(&self, _task: &Task<Self>) {}
+    fn release(&self, _task: Task<Self>) {}
+    fn release_local(&self, _task: &Task<Self>) {}
+    fn schedule(&self, _task: Task<Self>) {}
+}
+
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        raw()
+    }
+    fn no_op(_: *const ()) {}
+    fn raw() -> RawWaker {
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    unsafe { Waker::from_raw(raw()) }
+}
+
+/// A future that always returns `Pending`, so a task running it parks idle
+/// (rather than completing) after its first poll.
+struct Never;
+
+impl Future for Never {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        Poll::Pending
+    }
+}
+
+#[test]
+fn abort_idle_task_does_not_steal_a_live_reference() {
+    let (task, join) = joinable::<_, NoopSchedule>(Never);
+
+    // Drive the task once so it parks idle instead of being cancelled
+    // before ever being polled.
+    assert!(task.run(|| None).is_none());
+
+    // Hold a second, independent handle to the task across the abort, so
+    // there is something other than `join` left alive to observe the bug:
+    // if `cancel`'s idle branch dropped a reference it never acquired, the
+    // allocation would be freed while this `AbortHandle` still believes it
+    // holds a live one.
+    let abort_handle = join.abort_handle();
+
+    join.abort();
+    drop(abort_handle);
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut join = join;
+    match Pin::new(&mut join).poll(&mut cx) {
+        Poll::Ready(result) => assert!(result.unwrap_err().is_cancelled()),
+        Poll::Pending => panic!("an aborted, idle task should resolve immediately"),
+    }
+}
+
+#[test]
+fn concurrent_abort_of_idle_task_does_not_double_free() {
+    // A single `.abort()` call is trivially safe; the bug this guards
+    // against only shows up when several callers race to cancel the same
+    // idle task at once. `transition_to_cancelled` claims completion
+    // atomically (in the same compare-and-swap that sets `CANCELLED`), so
+    // exactly one of these racing callers ever calls `complete` for a given
+    // idle task; if that guarantee ever regresses back to every racer
+    // deciding independently (via a non-atomic `is_complete`/`is_running`
+    // check), `complete` runs more than once, tearing through the shared
+    // `UnsafeCell`s it writes and performing more `ref_dec`s than were ever
+    // acquired, freeing the allocation while some of the `AbortHandle`s used
+    // below still believe they hold a live reference to it.
+    use std::sync::Arc;
+
+    for _ in 0..200 {
+        let (task, join) = joinable::<_, NoopSchedule>(Never);
+        assert!(task.run(|| None).is_none());
+
+        const N: usize = 8;
+        let barrier = Arc::new(std::sync::Barrier::new(N));
+        let handles: Vec<_> = (0..N).map(|_| join.abort_handle()).collect();
+
+        let threads: Vec<_> = handles
+            .into_iter()
+            .map(|handle| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    handle.abort();
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        drop(join);
+    }
+}
+
+#[test]
+fn abort_before_first_poll_is_a_no_op_once_already_complete() {
+    let (task, join) = joinable::<_, NoopSchedule>(async {});
+
+    join.abort();
+    // Aborting before the task has a chance to run means `run` finishes it
+    // as cancelled without ever polling its future; this then
+    // exercises that `cancel`'s "already complete" fast path (taken
+    // the *second* time something tries to finish the task) does not touch
+    // the reference count at all.
+    assert!(task.run(|| None).is_none());
+    join.abort();
+}
+
+/// A future that panics with a known payload on its first poll.
+struct Panics(&'static str);
+
+impl Future for Panics {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        panic!(self.0)
+    }
+}
+
+#[test]
+fn into_panic_recovers_the_original_payload() {
+    // The default panic hook would otherwise print this panic's message to
+    // stderr even though it is caught and turned into a `JoinError` below.
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let (task, join) = joinable::<_, NoopSchedule>(Panics("boom"));
+    let result = task.run(|| None);
+    std::panic::set_hook(previous_hook);
+
+    assert!(result.is_none());
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut join = join;
+    let err = match Pin::new(&mut join).poll(&mut cx) {
+        Poll::Ready(Err(err)) => err,
+        Poll::Ready(Ok(())) => panic!("a panicking task should not complete successfully"),
+        Poll::Pending => panic!("a task that already panicked should resolve immediately"),
+    };
+
+    assert!(err.is_panic());
+    let payload = err.into_panic();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+}
+
+#[test]
+fn try_into_panic_on_a_cancelled_task_returns_the_join_error_unchanged() {
+    let (task, join) = joinable::<_, NoopSchedule>(Never);
+    assert!(task.run(|| None).is_none());
+
+    join.abort();
+
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut join = join;
+    let err = match Pin::new(&mut join).poll(&mut cx) {
+        Poll::Ready(Err(err)) => err,
+        Poll::Ready(Ok(())) => panic!("an aborted task should not complete successfully"),
+        Poll::Pending => panic!("an aborted, idle task should resolve immediately"),
+    };
+
+    assert!(!err.is_panic());
+    let err = err.try_into_panic().unwrap_err();
+    assert!(err.is_cancelled());
+}
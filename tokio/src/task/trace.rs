@@ -0,0 +1,60 @@
+//! Optional task instrumentation, enabled via the `tracing` feature.
+//!
+//! Each lifecycle event a task goes through (spawn, schedule, poll
+//! start/end, completion) bumps a lifetime counter in `COUNTERS` and is
+//! also emitted as a `tracing` event under the `tokio::task` target. A task
+//! is also given its own `tracing::Span`, entered around every poll in
+//! `Task::run`. A `tracing` subscriber can use the span's enter/exit
+//! timestamps to measure poll durations and scheduling latency in addition
+//! to reading the counters directly.
+
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+/// Lifetime counts of task lifecycle events, tracked regardless of whether
+/// a `tracing` subscriber is attached.
+#[derive(Default)]
+pub(crate) struct Counters {
+    pub(crate) spawned: AtomicU64,
+    pub(crate) scheduled: AtomicU64,
+    pub(crate) polls_started: AtomicU64,
+    pub(crate) polls_ended: AtomicU64,
+    pub(crate) completed: AtomicU64,
+}
+
+pub(crate) static COUNTERS: Counters = Counters {
+    spawned: AtomicU64::new(0),
+    scheduled: AtomicU64::new(0),
+    polls_started: AtomicU64::new(0),
+    polls_ended: AtomicU64::new(0),
+    completed: AtomicU64::new(0),
+};
+
+/// Build the span a newly spawned task will be polled under.
+pub(crate) fn new_span() -> tracing::Span {
+    tracing::trace_span!(target: "tokio::task", "runtime.spawn")
+}
+
+pub(crate) fn spawned() {
+    COUNTERS.spawned.fetch_add(1, Relaxed);
+    tracing::trace!(target: "tokio::task", "spawn");
+}
+
+pub(crate) fn scheduled() {
+    COUNTERS.scheduled.fetch_add(1, Relaxed);
+    tracing::trace!(target: "tokio::task", "schedule");
+}
+
+pub(crate) fn poll_start() {
+    COUNTERS.polls_started.fetch_add(1, Relaxed);
+    tracing::trace!(target: "tokio::task", "poll_start");
+}
+
+pub(crate) fn poll_end() {
+    COUNTERS.polls_ended.fetch_add(1, Relaxed);
+    tracing::trace!(target: "tokio::task", "poll_end");
+}
+
+pub(crate) fn completed() {
+    COUNTERS.completed.fetch_add(1, Relaxed);
+    tracing::trace!(target: "tokio::task", "complete");
+}
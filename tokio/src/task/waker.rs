@@ -0,0 +1,92 @@
+//! Builds a `std::task::Waker` directly out of a task's `Header` pointer, so
+//! waking a task never needs to allocate.
+
+use crate::task::core::Header;
+use crate::task::harness::Harness;
+use crate::task::Schedule;
+
+use std::future::Future;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::task::{RawWaker, RawWakerVTable, Waker};
+
+/// A borrowed `Waker` for a task, valid for as long as the `Header` it was
+/// built from.
+pub(super) struct WakerRef<'a> {
+    waker: ManuallyDrop<Waker>,
+    _p: PhantomData<&'a Header>,
+}
+
+impl Deref for WakerRef<'_> {
+    type Target = Waker;
+
+    fn deref(&self) -> &Waker {
+        &self.waker
+    }
+}
+
+pub(super) fn waker_ref<T, S>(header: &Header) -> WakerRef<'_>
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    let raw = RawWaker::new(header as *const _ as *const (), waker_vtable::<T, S>());
+    let waker = unsafe { ManuallyDrop::new(Waker::from_raw(raw)) };
+    WakerRef {
+        waker,
+        _p: PhantomData,
+    }
+}
+
+fn waker_vtable<T, S>() -> &'static RawWakerVTable
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    &RawWakerVTable::new(
+        clone_waker::<T, S>,
+        wake::<T, S>,
+        wake_by_ref::<T, S>,
+        drop_waker::<T, S>,
+    )
+}
+
+unsafe fn clone_waker<T, S>(ptr: *const ()) -> RawWaker
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    let header = NonNull::new_unchecked(ptr as *mut Header);
+    header.as_ref().state.ref_inc();
+    RawWaker::new(ptr, waker_vtable::<T, S>())
+}
+
+unsafe fn wake<T, S>(ptr: *const ())
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    let header = NonNull::new_unchecked(ptr as *mut Header);
+    Harness::<T, S>::from_raw(header).schedule();
+    Harness::<T, S>::from_raw(header).drop_task_ref();
+}
+
+unsafe fn wake_by_ref<T, S>(ptr: *const ())
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    let header = NonNull::new_unchecked(ptr as *mut Header);
+    Harness::<T, S>::from_raw(header).schedule();
+}
+
+unsafe fn drop_waker<T, S>(ptr: *const ())
+where
+    T: Future + Send + 'static,
+    S: Schedule,
+{
+    let header = NonNull::new_unchecked(ptr as *mut Header);
+    Harness::<T, S>::from_raw(header).drop_task_ref();
+}